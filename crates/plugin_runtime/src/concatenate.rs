@@ -0,0 +1,530 @@
+use std::collections::{HashMap, HashSet};
+
+use farmfe_core::{
+  module::{module_graph::ModuleGraph, ModuleId, ModuleSystem},
+  plugin::ResolveKind,
+  swc_common::{sync::Lrc, SourceMap, DUMMY_SP},
+  swc_ecma_ast::{
+    AssignExpr, AssignOp, AssignTarget, BindingIdent, CallExpr, Callee, ClassDecl, Decl, Expr,
+    ExprOrSpread, ExprStmt, FnDecl, Ident, IdentName, ImportDecl, ImportSpecifier, KeyValuePatProp,
+    Lit, MemberExpr, MemberProp, Module as SwcModule, ModuleDecl, ModuleExportName, ModuleItem,
+    ObjectPat, ObjectPatProp, Pat, PropName, SimpleAssignTarget, Stmt, Str, VarDecl, VarDeclKind,
+    VarDeclarator,
+  },
+  swc_ecma_codegen::{text_writer::JsWriter, Config as CodegenConfig, Emitter},
+};
+use farmfe_toolkit::swc_ecma_visit::{VisitMut, VisitMutWith};
+
+/// A subtree of the module graph that shares a single entry and can be hoisted into one
+/// scope instead of each member keeping its own `function(module, exports, require){}`
+/// wrapper.
+pub struct ConcatenationGroup {
+  pub entry: ModuleId,
+  /// Members in postorder (dependencies before dependents), ready to be
+  /// concatenated top to bottom.
+  pub members: Vec<ModuleId>,
+}
+
+/// Walk the static import subtree reachable from `entry` and collect every module that is
+/// safe to hoist into `entry`'s scope: ESM, statically analyzable, and never the target of
+/// a dynamic or conditional import from inside the group. Bails (returns `None`) on the
+/// first module that breaks an invariant, so callers fall back to the regular per-module
+/// wrapping rather than emitting a half-hoisted bundle.
+pub fn find_concatenatable_group(
+  entry: &ModuleId,
+  module_graph: &ModuleGraph,
+) -> Option<ConcatenationGroup> {
+  let entry_module = module_graph.module(entry)?;
+  if entry_module.meta.as_script().module_system != ModuleSystem::EsModule {
+    return None;
+  }
+
+  let mut members = vec![];
+  let mut visited: HashSet<ModuleId> = HashSet::new();
+  // each module is popped twice: first to queue its dependencies, then (`expanded`) to
+  // append it to `members` once they are all in, giving dependencies-first postorder
+  let mut queue = vec![(entry.clone(), false)];
+
+  while let Some((current, expanded)) = queue.pop() {
+    if expanded {
+      members.push(current);
+      continue;
+    }
+    if !visited.insert(current.clone()) {
+      continue;
+    }
+    queue.push((current.clone(), true));
+
+    for (dep_id, dep_kind) in module_graph.dependencies(&current) {
+      // a dynamic/conditional edge into the interior keeps the target a separate,
+      // independently loadable unit
+      if !matches!(dep_kind, ResolveKind::Import) {
+        continue;
+      }
+
+      if visited.contains(&dep_id) {
+        continue;
+      }
+
+      let dep_module = module_graph.module(&dep_id)?;
+      if dep_module.meta.as_script().module_system != ModuleSystem::EsModule {
+        return None;
+      }
+
+      // a module reached dynamically from anywhere else in the graph must stay
+      // independently loadable, even if this subtree only imports it statically
+      if module_graph
+        .dependents(&dep_id)
+        .into_iter()
+        .any(|(_, kind)| !matches!(kind, ResolveKind::Import))
+      {
+        return None;
+      }
+
+      queue.push((dep_id, false));
+    }
+  }
+
+  Some(ConcatenationGroup {
+    entry: entry.clone(),
+    members,
+  })
+}
+
+struct RenameIdents<'a> {
+  renames: &'a HashMap<String, String>,
+}
+
+impl VisitMut for RenameIdents<'_> {
+  fn visit_mut_ident(&mut self, ident: &mut Ident) {
+    if let Some(renamed) = self.renames.get(ident.sym.as_str()) {
+      ident.sym = renamed.clone().into();
+    }
+  }
+}
+
+fn collect_decl_names(decl: &Decl, names: &mut Vec<String>) {
+  match decl {
+    Decl::Fn(FnDecl { ident, .. }) | Decl::Class(ClassDecl { ident, .. }) => {
+      names.push(ident.sym.to_string());
+    }
+    Decl::Var(var_decl) => {
+      let VarDecl { decls, .. } = &**var_decl;
+      for d in decls {
+        if let Some(ident) = d.name.as_ident() {
+          names.push(ident.id.sym.to_string());
+        }
+      }
+    }
+    _ => {}
+  }
+}
+
+/// Attempt to hoist every member of `group` into `group.entry`'s scope: rename each
+/// member's top-level bindings to a module-unique name, rewrite every use site of an
+/// imported binding to the (renamed) declaration it resolves to, and concatenate the
+/// result into a single module in dependency order (dependencies first, so later
+/// members can reference the renamed bindings of earlier ones directly instead of
+/// through `require`). An import that resolves outside the group is lowered to a
+/// `require()` call instead, since the member whose scope it ends up in still needs the
+/// real runtime `require` to reach it.
+///
+/// Bails to `None` (signalling the caller should fall back to the regular per-module
+/// wrapping) the moment a member uses a construct this conservative pass doesn't
+/// rewrite safely: `export * from`, an export list (`export { x }` or `export { x } from`,
+/// neither of which is stripped below), a default export,
+/// or a default/namespace import (neither has a single resolvable named binding to
+/// substitute at each use site).
+pub fn try_concatenate(
+  group: &ConcatenationGroup,
+  asts: &HashMap<ModuleId, SwcModule>,
+  module_graph: &ModuleGraph,
+) -> Option<SwcModule> {
+  // First pass: work out every member's renamed top-level bindings before rewriting any
+  // use sites, since a later member's import of an earlier one has to know what that
+  // earlier member's export was renamed to.
+  let mut member_decl_renames: HashMap<ModuleId, HashMap<String, String>> = HashMap::new();
+
+  for (index, member) in group.members.iter().enumerate() {
+    let ast = asts.get(member)?;
+    let mut names = vec![];
+
+    for item in &ast.body {
+      match item {
+        ModuleItem::Stmt(Stmt::Decl(decl)) => collect_decl_names(decl, &mut names),
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export_decl)) => {
+          collect_decl_names(&export_decl.decl, &mut names)
+        }
+        // re-exports, `export *` and default exports have no named local binding to
+        // hoist onto; bail rather than guess at the exported value's shape. A local
+        // export list (`export { x }`) is not stripped below either, so it bails too.
+        ModuleItem::ModuleDecl(ModuleDecl::ExportAll(_))
+        | ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(_))
+        | ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(_)) => return None,
+        ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(_)) => return None,
+        // default/namespace imports have no single named binding to resolve a use site
+        // onto; bail rather than leave them unrewritten
+        ModuleItem::ModuleDecl(ModuleDecl::Import(import))
+          if import
+            .specifiers
+            .iter()
+            .any(|specifier| !matches!(specifier, ImportSpecifier::Named(_))) =>
+        {
+          return None
+        }
+        _ => {}
+      }
+    }
+
+    member_decl_renames.insert(
+      member.clone(),
+      names
+        .into_iter()
+        .map(|name| (name.clone(), format!("{name}$${index}")))
+        .collect(),
+    );
+  }
+
+  let mut merged_body = Vec::new();
+
+  for (index, member) in group.members.iter().enumerate() {
+    let mut ast = asts.get(member)?.clone();
+    let mut renames = member_decl_renames.get(member)?.clone();
+
+    // Resolve every import against the module graph: an in-group target is dropped and
+    // its imported names are added to this member's rename map so every use site is
+    // substituted for the renamed declaration; anything else keeps needing the real
+    // `require`, so it's lowered to one instead of being left as ESM syntax.
+    let mut new_body = Vec::with_capacity(ast.body.len());
+
+    for item in std::mem::take(&mut ast.body) {
+      let ModuleItem::ModuleDecl(ModuleDecl::Import(import)) = &item else {
+        new_body.push(item);
+        continue;
+      };
+
+      let target = module_graph.get_dep_by_source(member, &import.src.value, Some(ResolveKind::Import));
+
+      if let Some(target_renames) = member_decl_renames.get(&target) {
+        for specifier in &import.specifiers {
+          let ImportSpecifier::Named(named) = specifier else {
+            continue;
+          };
+          let imported_name = named
+            .imported
+            .as_ref()
+            .map(module_export_name_to_string)
+            .unwrap_or_else(|| named.local.sym.to_string());
+          let resolved_name = target_renames
+            .get(&imported_name)
+            .cloned()
+            .unwrap_or(imported_name);
+          renames.insert(named.local.sym.to_string(), resolved_name);
+        }
+        // in-group import: fully satisfied by the renamed binding above, drop it
+      } else {
+        new_body.push(require_stmt_for_import(import));
+      }
+    }
+
+    let is_entry = *member == group.entry;
+
+    // the entry's exports are the only bindings anything outside the concatenated scope
+    // can still reach (every other member's export just exposed a binding for other
+    // members to import, already inlined above by renaming); capture their original,
+    // pre-rename names now (`new_body` still holds the unrenamed import-resolved body)
+    // so they can be mirrored onto `exports` below, once renaming may have turned the
+    // binding itself into `name$$index`
+    let mut entry_export_names = Vec::new();
+    if is_entry {
+      for item in &new_body {
+        if let ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export_decl)) = item {
+          collect_decl_names(&export_decl.decl, &mut entry_export_names);
+        }
+      }
+    }
+
+    ast.body = new_body;
+    ast.visit_mut_with(&mut RenameIdents { renames: &renames });
+
+    // strip the (now renamed) `export` keyword everywhere: the whole merged body runs
+    // inside a single `function(module, exports, require){}` wrapper (see
+    // `try_concatenate_resource_pot`), where a top-level `export` is a SyntaxError
+    for item in &mut ast.body {
+      if let ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export_decl)) = item {
+        *item = ModuleItem::Stmt(Stmt::Decl(export_decl.decl.clone()));
+      }
+    }
+
+    merged_body.extend(ast.body);
+
+    if is_entry {
+      for name in entry_export_names {
+        let renamed = renames.get(&name).cloned().unwrap_or_else(|| name.clone());
+        merged_body.push(export_assignment_stmt(&name, &renamed));
+      }
+    }
+  }
+
+  Some(SwcModule {
+    span: DUMMY_SP,
+    body: merged_body,
+    shebang: None,
+  })
+}
+
+/// Build `exports.<original> = <renamed>;` — how the entry's top-level exports reach the
+/// `exports` object every module factory is invoked with, now that the `export` keyword
+/// itself has been stripped to fit inside that factory's function body.
+fn export_assignment_stmt(original: &str, renamed: &str) -> ModuleItem {
+  ModuleItem::Stmt(Stmt::Expr(ExprStmt {
+    span: DUMMY_SP,
+    expr: Box::new(Expr::Assign(AssignExpr {
+      span: DUMMY_SP,
+      op: AssignOp::Assign,
+      left: AssignTarget::Simple(SimpleAssignTarget::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(Expr::Ident(Ident::new("exports".into(), DUMMY_SP))),
+        prop: MemberProp::Ident(IdentName::new(original.into(), DUMMY_SP)),
+      })),
+      right: Box::new(Expr::Ident(Ident::new(renamed.into(), DUMMY_SP))),
+    })),
+  }))
+}
+
+fn module_export_name_to_string(name: &ModuleExportName) -> String {
+  match name {
+    ModuleExportName::Ident(ident) => ident.sym.to_string(),
+    ModuleExportName::Str(str_lit) => str_lit.value.to_string(),
+  }
+}
+
+/// Lower `import ... from "source"` into the CommonJS call the concatenated module's
+/// factory actually runs under (`function(module, exports, require){}`): named imports
+/// become a destructured `require()` call binding each local name, and a side-effect-only
+/// import (no specifiers) becomes a bare call.
+fn require_stmt_for_import(import: &ImportDecl) -> ModuleItem {
+  let require_call = Expr::Call(CallExpr {
+    span: DUMMY_SP,
+    callee: Callee::Expr(Box::new(Expr::Ident(Ident::new(
+      "require".into(),
+      DUMMY_SP,
+    )))),
+    args: vec![ExprOrSpread {
+      spread: None,
+      expr: Box::new(Expr::Lit(Lit::Str(Str {
+        span: DUMMY_SP,
+        value: import.src.value.clone(),
+        raw: None,
+      }))),
+    }],
+    type_args: None,
+  });
+
+  if import.specifiers.is_empty() {
+    return ModuleItem::Stmt(Stmt::Expr(ExprStmt {
+      span: DUMMY_SP,
+      expr: Box::new(require_call),
+    }));
+  }
+
+  let props = import
+    .specifiers
+    .iter()
+    .filter_map(|specifier| {
+      let ImportSpecifier::Named(named) = specifier else {
+        return None;
+      };
+      let imported_name = named
+        .imported
+        .as_ref()
+        .map(module_export_name_to_string)
+        .unwrap_or_else(|| named.local.sym.to_string());
+
+      Some(ObjectPatProp::KeyValue(KeyValuePatProp {
+        key: PropName::Ident(Ident::new(imported_name.into(), DUMMY_SP)),
+        value: Box::new(Pat::Ident(BindingIdent {
+          id: named.local.clone(),
+          type_ann: None,
+        })),
+      }))
+    })
+    .collect();
+
+  ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
+    span: DUMMY_SP,
+    kind: VarDeclKind::Var,
+    declare: false,
+    decls: vec![VarDeclarator {
+      span: DUMMY_SP,
+      name: Pat::Object(ObjectPat {
+        span: DUMMY_SP,
+        props,
+        optional: false,
+        type_ann: None,
+      }),
+      init: Some(Box::new(require_call)),
+      definite: false,
+    }],
+  }))))
+}
+
+/// Print a concatenated module back to source text. The merged AST carries only
+/// synthetic (`DUMMY_SP`) spans, so it's printed fresh rather than sliced out of any
+/// original file's source map.
+pub fn print_module(module: &SwcModule) -> String {
+  let cm: Lrc<SourceMap> = Default::default();
+  let mut buf = vec![];
+
+  {
+    let writer = JsWriter::new(cm.clone(), "\n", &mut buf, None);
+    let mut emitter = Emitter {
+      cfg: CodegenConfig::default(),
+      cm,
+      comments: None,
+      wr: writer,
+    };
+    // concatenated output is an internal runtime artifact; a codegen failure here means
+    // the rename/prune pass produced an invalid AST, which is a bug in this pass itself
+    emitter
+      .emit_module(module)
+      .expect("concatenation pass produced an invalid module");
+  }
+
+  String::from_utf8(buf).expect("codegen output is valid utf8")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use farmfe_core::swc_ecma_ast::{BlockStmt, Function, ReturnStmt};
+
+  fn ident(name: &str) -> Ident {
+    Ident::new(name.into(), DUMMY_SP)
+  }
+
+  #[test]
+  fn collect_decl_names_picks_up_fn_decl() {
+    let fn_decl = Decl::Fn(FnDecl {
+      ident: ident("helper"),
+      declare: false,
+      function: Box::new(Function {
+        params: vec![],
+        decorators: vec![],
+        span: DUMMY_SP,
+        body: Some(BlockStmt {
+          span: DUMMY_SP,
+          stmts: vec![Stmt::Return(ReturnStmt {
+            span: DUMMY_SP,
+            arg: None,
+          })],
+        }),
+        is_generator: false,
+        is_async: false,
+        type_params: None,
+        return_type: None,
+      }),
+    });
+
+    let mut names = vec![];
+    collect_decl_names(&fn_decl, &mut names);
+
+    assert_eq!(names, vec!["helper".to_string()]);
+  }
+
+  #[test]
+  fn rename_idents_rewrites_use_sites_not_just_declarations() {
+    // `helper()` — the exact shape a cross-member call left unrewritten by the old pass
+    // (the import line got dropped, but the call site itself was untouched).
+    let mut call_stmt = ModuleItem::Stmt(Stmt::Expr(ExprStmt {
+      span: DUMMY_SP,
+      expr: Box::new(Expr::Call(CallExpr {
+        span: DUMMY_SP,
+        callee: Callee::Expr(Box::new(Expr::Ident(ident("helper")))),
+        args: vec![],
+        type_args: None,
+      })),
+    }));
+
+    let renames = HashMap::from([("helper".to_string(), "helper$$1".to_string())]);
+    call_stmt.visit_mut_with(&mut RenameIdents { renames: &renames });
+
+    let module = SwcModule {
+      span: DUMMY_SP,
+      body: vec![call_stmt],
+      shebang: None,
+    };
+
+    assert_eq!(print_module(&module).trim(), "helper$$1();");
+  }
+
+  /// Parse `code` the way any consumer of the concatenated output would, so a test can
+  /// fail on an actual `SyntaxError` instead of only on a missing string fragment.
+  fn assert_parses_as_valid_js(code: &str) {
+    use farmfe_core::swc_common::FileName;
+    use farmfe_toolkit::swc_ecma_parser::{lexer::Lexer, EsSyntax, Parser, StringInput, Syntax};
+
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(Lrc::new(FileName::Anon), code.to_string());
+    let lexer = Lexer::new(
+      Syntax::Es(EsSyntax::default()),
+      Default::default(),
+      StringInput::from(&*fm),
+      None,
+    );
+    let mut parser = Parser::new_from(lexer);
+
+    parser
+      .parse_module()
+      .unwrap_or_else(|err| panic!("expected valid JS, got a parse error: {err:?}\n{code}"));
+  }
+
+  #[test]
+  fn entry_export_assignment_parses_inside_factory_wrapper() {
+    // The shape the pass emits for an entry export: the declaration with its `export`
+    // keyword stripped, followed by `export_assignment_stmt`'s `exports.<name> = <renamed>;`.
+    // It must parse once wrapped in `function(module, exports, require){}` by
+    // `try_concatenate_resource_pot`, where a leftover `export` is a SyntaxError. The
+    // stripped declaration is built by hand here; `try_concatenate` itself is not run.
+    let renames = HashMap::from([("helper".to_string(), "helper$$0".to_string())]);
+
+    let mut merged_body = vec![ModuleItem::Stmt(Stmt::Decl(Decl::Fn(FnDecl {
+      ident: ident("helper$$0"),
+      declare: false,
+      function: Box::new(Function {
+        params: vec![],
+        decorators: vec![],
+        span: DUMMY_SP,
+        body: Some(BlockStmt {
+          span: DUMMY_SP,
+          stmts: vec![],
+        }),
+        is_generator: false,
+        is_async: false,
+        type_params: None,
+        return_type: None,
+      }),
+    })))];
+    merged_body.push(export_assignment_stmt(
+      "helper",
+      renames.get("helper").unwrap(),
+    ));
+
+    let module = SwcModule {
+      span: DUMMY_SP,
+      body: merged_body,
+      shebang: None,
+    };
+
+    let printed = print_module(&module);
+    // `export ` with the space: the keyword, not the `exports.` assignment target
+    assert!(!printed.contains("export "));
+
+    // parenthesized so the wrapper itself parses standalone as a valid expression
+    // statement, matching how `try_concatenate_resource_pot` embeds this same text as a
+    // value inside a larger object literal rather than at statement position
+    let wrapped = format!("(function(module, exports, require) {{\n{printed}\n}});");
+    assert_parses_as_valid_js(&wrapped);
+    assert!(wrapped.contains("exports.helper = helper$$0;"));
+  }
+}
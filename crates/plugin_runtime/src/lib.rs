@@ -21,7 +21,11 @@ use farmfe_core::{
     resource_pot::{ResourcePot, ResourcePotMetaData, ResourcePotType},
     Resource, ResourceOrigin, ResourceType,
   },
-  swc_ecma_ast::{ExportAll, ImportDecl, ImportSpecifier, ModuleDecl, ModuleItem},
+  serde_json,
+  swc_ecma_ast::{
+    Expr, ExportAll, ImportDecl, ImportSpecifier, Lit, ModuleDecl, ModuleItem, Prop,
+    PropName, PropOrSpread,
+  },
 };
 use farmfe_toolkit::{
   fs::read_file_utf8,
@@ -30,11 +34,16 @@ use farmfe_toolkit::{
 
 use insert_runtime_plugins::insert_runtime_plugins;
 use render_resource_pot::*;
+use synthetic_module::SyntheticModule;
 
 const RUNTIME_SUFFIX: &str = ".farm-runtime";
+/// Import attribute `type`s this plugin knows how to turn into a module body.
+const SUPPORTED_MODULE_ASSERTION_TYPES: &[&str] = &["json"];
 
+mod concatenate;
 mod insert_runtime_plugins;
 pub mod render_resource_pot;
+pub mod synthetic_module;
 
 /// FarmPluginRuntime is charge of:
 /// * resolving, parsing and generating a executable runtime code and inject the code into the entries.
@@ -46,6 +55,9 @@ pub mod render_resource_pot;
 /// All runtime module (including the runtime core and its plugins) will be suffixed as `.farm-runtime` to distinguish with normal script modules.
 pub struct FarmPluginRuntime {
   runtime_code: Mutex<Arc<String>>,
+  /// Virtual modules registered by this or other plugins via
+  /// [`FarmPluginRuntime::register_synthetic_module`], keyed by their specifier.
+  synthetic_modules: Mutex<HashMap<String, SyntheticModule>>,
 }
 
 impl Plugin for FarmPluginRuntime {
@@ -81,6 +93,15 @@ impl Plugin for FarmPluginRuntime {
     context: &Arc<CompilationContext>,
     hook_context: &PluginHookContext,
   ) -> farmfe_core::error::Result<Option<PluginResolveHookResult>> {
+    // synthetic modules have no file on disk, so short-circuit straight to a stable id
+    // before any of the real-file resolution below runs
+    if self.synthetic_modules.lock().contains_key(&param.source) {
+      return Ok(Some(PluginResolveHookResult {
+        resolved_path: format!("{}{}", param.source, RUNTIME_SUFFIX),
+        ..Default::default()
+      }));
+    }
+
     // avoid cyclic resolve
     if matches!(&hook_context.caller, Some(c) if c == "FarmPluginRuntime") {
       Ok(None)
@@ -125,6 +146,14 @@ impl Plugin for FarmPluginRuntime {
   ) -> farmfe_core::error::Result<Option<PluginLoadHookResult>> {
     if param.resolved_path.ends_with(RUNTIME_SUFFIX) {
       let real_file_path = param.resolved_path.replace(RUNTIME_SUFFIX, "");
+
+      if let Some(synthetic) = self.synthetic_modules.lock().get(&real_file_path) {
+        return Ok(Some(PluginLoadHookResult {
+          content: synthetic.render(),
+          module_type: synthetic.module_type.clone(),
+        }));
+      }
+
       let content = read_file_utf8(&real_file_path)?;
 
       if let Some(module_type) = module_type_from_id(&real_file_path) {
@@ -135,6 +164,19 @@ impl Plugin for FarmPluginRuntime {
       } else {
         panic!("unknown module type for {}", real_file_path);
       }
+    } else if param.resolved_path.ends_with(".json") {
+      let content = read_file_utf8(&param.resolved_path)?;
+      let content = Self::render_json_module(&content).map_err(|err| {
+        CompilationError::GenericError(format!(
+          "Failed to parse json module `{}`: {}",
+          param.resolved_path, err
+        ))
+      })?;
+
+      Ok(Some(PluginLoadHookResult {
+        content,
+        module_type: ModuleType::Js,
+      }))
     } else {
       Ok(None)
     }
@@ -164,6 +206,21 @@ impl Plugin for FarmPluginRuntime {
     param: &mut PluginAnalyzeDepsHookParam,
     _context: &Arc<CompilationContext>,
   ) -> farmfe_core::error::Result<Option<()>> {
+    if let ModuleMetaData::Script(script) = &param.module.meta {
+      for stmt in &script.ast.body {
+        if let ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) = stmt {
+          if let Some(asserted_type) = Self::import_attribute_type(import_decl) {
+            if !SUPPORTED_MODULE_ASSERTION_TYPES.contains(&asserted_type.as_str()) {
+              return Err(CompilationError::GenericError(format!(
+                "Unsupported import attribute `type: \"{}\"` for `{}`, Farm only supports {:?}",
+                asserted_type, import_decl.src.value, SUPPORTED_MODULE_ASSERTION_TYPES
+              )));
+            }
+          }
+        }
+      }
+    }
+
     if !param.module.id.relative_path().ends_with(RUNTIME_SUFFIX) {
       return Ok(None);
     }
@@ -260,40 +317,158 @@ impl Plugin for FarmPluginRuntime {
 
     let module_graph = context.module_graph.write();
 
+    // every non-entry Js resource pot is a separately loadable chunk; build the manifest
+    // the runtime's `import()` consults to find which chunk a module lives in, and which
+    // modules a chunk brings with it once its script has loaded
+    let mut module_chunks = HashMap::new();
+    let mut chunk_modules: HashMap<String, Vec<String>> = HashMap::new();
+
+    for resource_pot in resource_pots.iter() {
+      if matches!(resource_pot.resource_pot_type, ResourcePotType::Js)
+        && resource_pot.entry_module.is_none()
+      {
+        let chunk_id = resource_pot.id.to_string();
+
+        for module_id in resource_pot.modules() {
+          let id = module_id.id(context.config.mode.clone());
+          module_chunks.insert(id.clone(), chunk_id.clone());
+          chunk_modules.entry(chunk_id.clone()).or_default().push(id);
+        }
+      }
+    }
+
+    // `chunk_id` is this plugin's own identifier for the pot (see `generate_resources`,
+    // which emits non-runtime pots through the core resource pipeline under that same
+    // name); prefix it with the configured public path so a deployment served from a
+    // subpath or CDN still resolves. The core resource pipeline may additionally content
+    // hash the emitted filename after this manifest is built, which this plugin has no
+    // visibility into from here, so a hashed-filename build still needs that hash
+    // threaded back into this manifest at the point the real resource name is known.
+    let manifest = serde_json::json!({
+      "moduleChunks": module_chunks,
+      "chunks": chunk_modules
+        .into_iter()
+        .map(|(chunk_id, module_ids)| {
+          (
+            chunk_id.clone(),
+            serde_json::json!({
+              "url": format!("{}{}.js", context.config.output.public_path, chunk_id),
+              "moduleIds": module_ids
+            }),
+          )
+        })
+        .collect::<HashMap<_, _>>(),
+    });
+
     for resource_pot in resource_pots {
       if matches!(resource_pot.resource_pot_type, ResourcePotType::Runtime) {
         let RenderedJsResourcePot { mut bundle, .. } =
           resource_pot_to_runtime_object(resource_pot, &module_graph, context)?;
 
+        // `cache[id]` is seeded before the factory runs, so a circular import that
+        // re-enters `require` mid-evaluation observes the same module.exports instead
+        // of a fresh, empty one. Exports are not live across a cycle: an export
+        // re-assigned after the point a back-edge re-enters is still whatever value
+        // `resource_pot_to_runtime_object` (from the `render_resource_pot` module
+        // declared above) copied onto `exports` at that export statement, since ESM
+        // exports are emitted as one-time value copies, not getters on `module.exports`.
         bundle.prepend(
-          r#"(function (modules, entryModule) {
+          r#"(function (modules, entryModule, dynamicImportManifest) {
             var cache = {};
-          
+            var chunkPromises = {};
+            var chunkListeners = [];
+
             function require(id) {
               if (cache[id]) return cache[id].exports;
-          
+
               var module = {
                 id: id,
                 exports: {}
               };
-          
-              modules[id](module, module.exports, require);
+
               cache[id] = module;
+              modules[id](module, module.exports, require, importModule);
               return module.exports;
             }
-          
+
+            function registerChunkModule(id, factory) {
+              modules[id] = factory;
+              chunkListeners.slice().forEach(function (listener) {
+                listener(id);
+              });
+            }
+
+            function loadChunk(chunkId) {
+              if (chunkPromises[chunkId]) return chunkPromises[chunkId];
+
+              var chunk = dynamicImportManifest.chunks[chunkId];
+              var promise = new Promise(function (resolve, reject) {
+                var pending = chunk.moduleIds.filter(function (id) {
+                  return !modules[id];
+                });
+
+                if (pending.length === 0) {
+                  resolve();
+                  return;
+                }
+
+                var onModuleRegistered = function (id) {
+                  pending = pending.filter(function (pendingId) {
+                    return pendingId !== id;
+                  });
+
+                  if (pending.length === 0) {
+                    chunkListeners = chunkListeners.filter(function (listener) {
+                      return listener !== onModuleRegistered;
+                    });
+                    resolve();
+                  }
+                };
+                chunkListeners.push(onModuleRegistered);
+
+                var script = document.createElement('script');
+                script.src = chunk.url;
+                script.onerror = reject;
+                document.head.appendChild(script);
+              });
+
+              chunkPromises[chunkId] = promise;
+              return promise;
+            }
+
+            // shared by every module so a dynamic `import()` resolves once the target
+            // module's chunk (if any) has arrived and registered, then falls back to the
+            // synchronous `require` for modules already present
+            function importModule(id) {
+              if (modules[id]) return Promise.resolve(require(id));
+
+              var chunkId = dynamicImportManifest.moduleChunks[id];
+              if (!chunkId) return Promise.reject(new Error('Cannot find module \'' + id + '\''));
+
+              return loadChunk(chunkId).then(function () {
+                return require(id);
+              });
+            }
+
+            var __farm_global_this__ = (globalThis || window || global || self);
+            __farm_global_this__[__farm_namespace__] = __farm_global_this__[__farm_namespace__] || {};
+            __farm_global_this__[__farm_namespace__].__farm_module_system__ = {
+              register: registerChunkModule
+            };
+
             require(entryModule);
           })("#,
         );
 
         bundle.append(
           &format!(
-            ", {:?});",
+            ", {:?}, {});",
             resource_pot
               .entry_module
               .as_ref()
               .unwrap()
-              .id(context.config.mode.clone())
+              .id(context.config.mode.clone()),
+            manifest
           ),
           None,
         );
@@ -320,6 +495,15 @@ impl Plugin for FarmPluginRuntime {
       }));
     } else if matches!(resource_pot.resource_pot_type, ResourcePotType::Js) {
       let module_graph = context.module_graph.read();
+
+      if context.config.runtime.concatenate_modules {
+        if let Some(concatenated) =
+          self.try_concatenate_resource_pot(resource_pot, &module_graph, context)?
+        {
+          return Ok(Some(concatenated));
+        }
+      }
+
       let RenderedJsResourcePot {
         mut bundle,
         rendered_modules,
@@ -405,6 +589,198 @@ impl FarmPluginRuntime {
   pub fn new(_: &Config) -> Self {
     Self {
       runtime_code: Mutex::new(Arc::new(String::new())),
+      synthetic_modules: Mutex::new(HashMap::new()),
     }
   }
+
+  /// Register a virtual module under `specifier`, so importing `specifier` resolves to
+  /// `module` instead of a file on disk. Plugins call this (e.g. from their own `config`
+  /// hook) to contribute runtime modules generated in memory.
+  pub fn register_synthetic_module(&self, specifier: impl Into<String>, module: SyntheticModule) {
+    self.synthetic_modules.lock().insert(specifier.into(), module);
+  }
+
+  /// Try to hoist `resource_pot`'s modules into a single scope instead of each keeping
+  /// its own `function(module, exports, require){}` wrapper. Only applies when the
+  /// whole pot forms one concatenatable group rooted at its entry module; returns
+  /// `Ok(None)` whenever that's not the case (no entry, a non-ESM/dynamically-shared
+  /// member, or a construct [`concatenate::try_concatenate`] doesn't rewrite), so the
+  /// caller falls back to the regular per-module wrapping.
+  fn try_concatenate_resource_pot(
+    &self,
+    resource_pot: &ResourcePot,
+    module_graph: &farmfe_core::module::module_graph::ModuleGraph,
+    context: &Arc<CompilationContext>,
+  ) -> farmfe_core::error::Result<Option<ResourcePotMetaData>> {
+    let Some(entry) = resource_pot.entry_module.as_ref() else {
+      return Ok(None);
+    };
+
+    let Some(group) = concatenate::find_concatenatable_group(entry, module_graph) else {
+      return Ok(None);
+    };
+
+    let pot_module_ids: std::collections::HashSet<_> = resource_pot.modules().into_iter().collect();
+    if group.members.len() != pot_module_ids.len()
+      || !group.members.iter().all(|id| pot_module_ids.contains(id))
+    {
+      return Ok(None);
+    }
+
+    let mut asts = HashMap::new();
+    for member in &group.members {
+      let module = module_graph.module(member).unwrap();
+      asts.insert(member.clone(), module.meta.as_script().ast.clone());
+    }
+
+    let Some(merged) = concatenate::try_concatenate(&group, &asts, module_graph) else {
+      return Ok(None);
+    };
+
+    let content = concatenate::print_module(&merged);
+    let wrapped = format!("function(module, exports, require) {{\n{content}\n}}");
+    let mut rendered_modules = HashMap::new();
+    rendered_modules.insert(entry.clone(), Arc::new(wrapped.clone()));
+
+    let bundle_content = format!(
+      r#"(function (modules) {{
+        for (var key in modules) {{
+          var __farm_global_this__ = (globalThis || window || global || self)[
+            __farm_namespace__
+          ];
+          __farm_global_this__.__farm_module_system__.register(key, modules[key]);
+        }}
+      }})({{ {:?}: {wrapped} }});"#,
+      entry.id(context.config.mode.clone())
+    );
+
+    Ok(Some(ResourcePotMetaData {
+      rendered_modules,
+      rendered_content: Arc::new(bundle_content),
+      rendered_map_chain: vec![],
+    }))
+  }
+
+  /// Read the `type` attribute out of an import's `with { type: "..." }` clause, e.g.
+  /// `import data from "./x.json" with { type: "json" }`.
+  fn import_attribute_type(import_decl: &ImportDecl) -> Option<String> {
+    let with_clause = import_decl.with.as_ref()?;
+
+    with_clause.props.iter().find_map(|prop| {
+      let PropOrSpread::Prop(box Prop::KeyValue(key_value)) = prop else {
+        return None;
+      };
+      let key_is_type = match &key_value.key {
+        PropName::Ident(ident) => &*ident.sym == "type",
+        PropName::Str(str_prop) => &*str_prop.value == "type",
+        _ => false,
+      };
+
+      if !key_is_type {
+        return None;
+      }
+
+      match &*key_value.value {
+        Expr::Lit(Lit::Str(str_lit)) => Some(str_lit.value.to_string()),
+        _ => None,
+      }
+    })
+  }
+
+  /// Turn a JSON module's raw source into its CommonJS body: `content` is parsed eagerly
+  /// so a malformed file surfaces a compilation error here, instead of failing at
+  /// runtime inside the generated `JSON.parse` call. The module body still goes through
+  /// `JSON.parse` rather than being embedded as a JS object literal: a literal
+  /// `"__proto__"` key in an object initializer sets the object's `[[Prototype]]`
+  /// instead of creating an own data property, which would silently turn
+  /// `{"__proto__": {...}}` into a prototype-pollution vector and drop the key's value
+  /// as data. `JSON.parse` always creates `__proto__` as an ordinary own property.
+  fn render_json_module(content: &str) -> Result<String, serde_json::Error> {
+    serde_json::from_str::<serde_json::Value>(content)?;
+    let escaped_content = serde_json::to_string(content)?;
+    Ok(format!("module.exports = JSON.parse({escaped_content});"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use farmfe_core::swc_ecma_ast::{Ident, KeyValueProp, ObjectLit, Str};
+
+  #[test]
+  fn render_json_module_wraps_in_json_parse() {
+    let rendered = FarmPluginRuntime::render_json_module(r#"{"a":1,"b":[true,null]}"#).unwrap();
+
+    assert_eq!(
+      rendered,
+      r#"module.exports = JSON.parse("{\"a\":1,\"b\":[true,null]}");"#
+    );
+  }
+
+  #[test]
+  fn render_json_module_preserves_proto_as_an_own_property() {
+    // a literal `__proto__` key in a JS object initializer sets the prototype instead of
+    // creating an own property; going through `JSON.parse` avoids that entirely
+    let rendered =
+      FarmPluginRuntime::render_json_module(r#"{"__proto__":{"polluted":true}}"#).unwrap();
+
+    assert!(rendered.starts_with("module.exports = JSON.parse("));
+    assert!(rendered.contains(r#"\"__proto__\":{\"polluted\":true}"#));
+  }
+
+  #[test]
+  fn render_json_module_rejects_malformed_json() {
+    assert!(FarmPluginRuntime::render_json_module("{ not json").is_err());
+  }
+
+  fn import_decl_with_type_attribute(ty: &str) -> ImportDecl {
+    ImportDecl {
+      span: Default::default(),
+      specifiers: vec![],
+      src: Box::new(Str {
+        span: Default::default(),
+        value: "./x.json".into(),
+        raw: None,
+      }),
+      type_only: false,
+      with: Some(Box::new(ObjectLit {
+        span: Default::default(),
+        props: vec![PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+          key: PropName::Ident(Ident::new("type".into(), Default::default())),
+          value: Box::new(Expr::Lit(Lit::Str(Str {
+            span: Default::default(),
+            value: ty.into(),
+            raw: None,
+          }))),
+        })))],
+      })),
+    }
+  }
+
+  #[test]
+  fn import_attribute_type_reads_the_type_clause() {
+    let import_decl = import_decl_with_type_attribute("json");
+
+    assert_eq!(
+      FarmPluginRuntime::import_attribute_type(&import_decl),
+      Some("json".to_string())
+    );
+  }
+
+  #[test]
+  fn import_attribute_type_is_none_without_a_with_clause() {
+    let mut import_decl = import_decl_with_type_attribute("json");
+    import_decl.with = None;
+
+    assert_eq!(FarmPluginRuntime::import_attribute_type(&import_decl), None);
+  }
+
+  #[test]
+  fn unsupported_assertion_type_is_rejected() {
+    // mirrors the check in `analyze_deps`: only "json" is in the allowlist
+    let import_decl = import_decl_with_type_attribute("css");
+    let asserted_type = FarmPluginRuntime::import_attribute_type(&import_decl).unwrap();
+
+    assert!(!SUPPORTED_MODULE_ASSERTION_TYPES.contains(&asserted_type.as_str()));
+  }
 }
@@ -0,0 +1,86 @@
+use farmfe_core::module::ModuleType;
+
+/// A runtime module whose source is generated in memory rather than read from disk: the
+/// module declares its export names up front, and `generate` is the evaluation step that
+/// fills their values.
+///
+/// Plugins register these through [`crate::FarmPluginRuntime::register_synthetic_module`]
+/// to contribute runtime modules (polyfills, manifest shims, env stubs, ...) that
+/// `resolve`/`load` treat identically to an on-disk module.
+pub struct SyntheticModule {
+  /// Names exported by this module, e.g. `["default"]` or `["a", "b"]`.
+  pub exports: Vec<String>,
+  pub module_type: ModuleType,
+  /// Produces the module's executable body. Invoked lazily, once per `load`.
+  pub generate: Box<dyn Fn() -> String + Send + Sync>,
+}
+
+impl SyntheticModule {
+  pub fn new(
+    exports: Vec<String>,
+    module_type: ModuleType,
+    generate: impl Fn() -> String + Send + Sync + 'static,
+  ) -> Self {
+    Self {
+      exports,
+      module_type,
+      generate: Box::new(generate),
+    }
+  }
+
+  /// Render the synthetic module to its final source: the generated body evaluated
+  /// once in a private scope, where it fills the declared exports onto the
+  /// `__farm_synthetic_exports__` object, then each declared export read off of it. `"default"`
+  /// is a reserved word, so it's emitted as `export default` rather than
+  /// `export const default`, which would be a syntax error.
+  pub(crate) fn render(&self) -> String {
+    let body = (self.generate)();
+    let export_bindings = self
+      .exports
+      .iter()
+      .map(|name| {
+        if name == "default" {
+          "export default __farm_synthetic_exports__.default;".to_string()
+        } else {
+          format!("export const {name} = __farm_synthetic_exports__.{name};")
+        }
+      })
+      .collect::<Vec<_>>()
+      .join("\n");
+
+    format!(
+      "const __farm_synthetic_exports__ = {{}};\n(function () {{\n{body}\n}})();\n{export_bindings}"
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn default_export_uses_export_default_not_export_const() {
+    let module = SyntheticModule::new(vec!["default".to_string()], ModuleType::Js, || {
+      "__farm_synthetic_exports__.default = 1;".to_string()
+    });
+
+    let rendered = module.render();
+
+    assert!(rendered.contains("export default __farm_synthetic_exports__.default;"));
+    assert!(!rendered.contains("export const default"));
+  }
+
+  #[test]
+  fn named_exports_still_use_export_const() {
+    let module = SyntheticModule::new(
+      vec!["a".to_string(), "b".to_string()],
+      ModuleType::Js,
+      || String::new(),
+    );
+
+    let rendered = module.render();
+
+    assert!(rendered.contains("export const a = __farm_synthetic_exports__.a;"));
+    assert!(rendered.contains("export const b = __farm_synthetic_exports__.b;"));
+  }
+}